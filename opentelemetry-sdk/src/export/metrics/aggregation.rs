@@ -0,0 +1,58 @@
+//! Support for aggregators exposing their collected state to an exporter.
+use crate::metrics::sdk_api::Number;
+use opentelemetry_api::metrics::Result;
+use std::time::SystemTime;
+
+/// Aggregation is an interface returned by the `Aggregator` containing an
+/// aggregated metric data point.
+pub trait Aggregation {
+    /// `kind` returns a short identifying string to identify the kind of
+    /// aggregator that produced this aggregation.
+    fn kind(&self) -> &AggregationKind;
+}
+
+/// AggregationKind identifies the kind of aggregation used to produce an
+/// [`Aggregation`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AggregationKind(&'static str);
+
+impl AggregationKind {
+    /// Used for aggregators reporting only the sum of updates.
+    pub const SUM: Self = AggregationKind("Sum");
+    /// Used for aggregators that maintain the last value observed.
+    pub const LAST_VALUE: Self = AggregationKind("Lastvalue");
+    /// Used for aggregators that maintain a fixed-boundary histogram of
+    /// observed values.
+    pub const HISTOGRAM: Self = AggregationKind("Histogram");
+    /// Used for aggregators that maintain a relative-error exponential
+    /// histogram (e.g. DDSketch) of observed values.
+    pub const EXPONENTIAL_HISTOGRAM: Self = AggregationKind("ExponentialHistogram");
+}
+
+/// Sum returns an aggregated sum.
+pub trait Sum {
+    /// The sum of the values of the aggregator's measurements.
+    fn sum(&self) -> Result<Number>;
+}
+
+/// LastValue returns the last-observed value and the time it was observed.
+pub trait LastValue {
+    /// The last-observed value and the time it was observed.
+    fn last_value(&self) -> Result<(Number, SystemTime)>;
+}
+
+/// Distribution returns the summary statistics for a distribution of
+/// measurements, including estimates for arbitrary quantiles.
+pub trait Distribution {
+    /// The smallest value observed.
+    fn min(&self) -> Result<Number>;
+    /// The largest value observed.
+    fn max(&self) -> Result<Number>;
+    /// The sum of all observed values.
+    fn sum(&self) -> Result<Number>;
+    /// The number of values observed.
+    fn count(&self) -> Result<u64>;
+    /// An estimate of the given quantile (in `[0.0, 1.0]`) of the observed
+    /// values, accurate to within the aggregator's configured error bound.
+    fn quantile(&self, quantile: f64) -> Result<Number>;
+}
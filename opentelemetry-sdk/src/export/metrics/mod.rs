@@ -0,0 +1,2 @@
+//! Interfaces for exporting metric data collected by the SDK.
+pub mod aggregation;
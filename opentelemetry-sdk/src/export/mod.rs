@@ -0,0 +1,2 @@
+//! Interfaces for exporting telemetry collected by the SDK.
+pub mod metrics;
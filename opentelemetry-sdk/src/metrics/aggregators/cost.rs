@@ -0,0 +1,361 @@
+use crate::metrics::aggregators::Aggregator;
+use opentelemetry_api::KeyValue;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem::size_of_val;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tracks the estimated memory cost of a set of live aggregators so the
+/// accumulator can enforce a `max_cost`/`max_cardinality` budget instead of
+/// growing one aggregator per unique attribute set without bound.
+///
+/// Costs are approximate: [`Aggregator::cost`] plus the serialized size of
+/// the aggregator's attribute set. The tracker only needs to be consistent
+/// with itself (increments/decrements must balance), not byte-exact with the
+/// allocator.
+#[derive(Debug, Default)]
+pub struct CostTracker {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl CostTracker {
+    /// Estimate the cost of storing `attributes` alongside an aggregator:
+    /// a rough accounting of the key/value bytes involved.
+    pub fn attributes_cost(attributes: &[KeyValue]) -> usize {
+        attributes
+            .iter()
+            .map(|kv| kv.key.as_str().len() + size_of_val(&kv.value))
+            .sum()
+    }
+
+    /// Record that `cost` additional bytes are now tracked, updating the
+    /// peak if this is a new high-water mark.
+    pub fn add(&self, cost: usize) {
+        let current = self.current.fetch_add(cost, Ordering::SeqCst) + cost;
+        self.peak.fetch_max(current, Ordering::SeqCst);
+    }
+
+    /// Record that `cost` bytes are no longer tracked, e.g. because the
+    /// aggregator they belonged to was checkpointed and reset, or evicted.
+    pub fn subtract(&self, cost: usize) {
+        self.current.fetch_sub(cost, Ordering::SeqCst);
+    }
+
+    /// The cost currently tracked.
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    /// The highest cost ever tracked, for diagnostics.
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+}
+
+/// The synthetic attribute used to tag measurements that overflowed the
+/// configured cardinality/cost limit and were routed to the shared overflow
+/// aggregator instead of a fresh per-attribute-set aggregator.
+pub const OVERFLOW_ATTRIBUTE_KEY: &str = "otel.metric.overflow";
+
+/// An attribute set used as a `HashMap` key.
+///
+/// `opentelemetry_api::Value` holds a bare `f64` for its numeric variant and
+/// only derives `PartialEq`, so a plain `Vec<KeyValue>` can't satisfy the
+/// `Eq + Hash` bound a `HashMap` key requires. This sorts the attributes by
+/// key (so two attribute sets built in different orders compare equal) and
+/// both compares and hashes each value by its debug representation, so
+/// `Eq` and `Hash` always agree (e.g. `0.0` and `-0.0` are distinct keys).
+#[derive(Debug, Clone)]
+struct AttributeSetKey(Vec<KeyValue>);
+
+impl AttributeSetKey {
+    fn new(attributes: &[KeyValue]) -> Self {
+        let mut sorted = attributes.to_vec();
+        sorted.sort_unstable_by(|a, b| a.key.as_str().cmp(b.key.as_str()));
+        AttributeSetKey(sorted)
+    }
+}
+
+impl PartialEq for AttributeSetKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .all(|(a, b)| {
+                    a.key == b.key && format!("{:?}", a.value) == format!("{:?}", b.value)
+                })
+    }
+}
+
+impl Eq for AttributeSetKey {}
+
+impl Hash for AttributeSetKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for kv in &self.0 {
+            kv.key.hash(state);
+            format!("{:?}", kv.value).hash(state);
+        }
+    }
+}
+
+/// A store of `T` keyed by attribute set that enforces a cost/cardinality
+/// bound: once the bound would be exceeded, new attribute sets are routed
+/// to a single shared overflow slot (tagged with
+/// [`OVERFLOW_ATTRIBUTE_KEY`]`=true`) rather than allocated a slot of their
+/// own, so memory stays bounded while totals remain correct.
+#[derive(Debug)]
+pub struct BoundedAggregatorStore<T: Aggregator> {
+    aggregators: HashMap<AttributeSetKey, (T, usize)>,
+    overflow: Option<T>,
+    tracker: CostTracker,
+    max_cost: usize,
+    max_cardinality: usize,
+}
+
+impl<T: Aggregator> BoundedAggregatorStore<T> {
+    /// Create a new store bounded by `max_cost` bytes and `max_cardinality`
+    /// distinct attribute sets, whichever limit is hit first.
+    pub fn new(max_cost: usize, max_cardinality: usize) -> Self {
+        BoundedAggregatorStore {
+            aggregators: HashMap::new(),
+            overflow: None,
+            tracker: CostTracker::default(),
+            max_cost,
+            max_cardinality,
+        }
+    }
+
+    /// Look up (or create, via `new_aggregator`) the aggregator for
+    /// `attributes`. Returns the aggregator to record the measurement
+    /// against, which may be the shared overflow aggregator if the bound
+    /// has been reached.
+    ///
+    /// Re-derives the tracked cost of an existing aggregator from
+    /// [`Aggregator::cost`] on every call, so a live aggregator that grows
+    /// over time (e.g. a ddsketch histogram accumulating buckets) still
+    /// counts against `max_cost` as it does, rather than only at creation.
+    pub fn get_or_create(
+        &mut self,
+        attributes: &[KeyValue],
+        new_aggregator: impl FnOnce() -> T,
+    ) -> &mut T {
+        let key = AttributeSetKey::new(attributes);
+
+        if self.aggregators.contains_key(&key) {
+            let entry = self.aggregators.get_mut(&key).unwrap();
+            let new_cost = entry.0.cost() + CostTracker::attributes_cost(attributes);
+            if new_cost > entry.1 {
+                self.tracker.add(new_cost - entry.1);
+            } else if new_cost < entry.1 {
+                self.tracker.subtract(entry.1 - new_cost);
+            }
+            entry.1 = new_cost;
+            return &mut self.aggregators.get_mut(&key).unwrap().0;
+        }
+
+        // Decide admission from the type's static size, not a constructed
+        // instance: a cardinality-exploding stream of new attribute sets is
+        // exactly the hot path this store exists to protect, and it must
+        // not pay for building (and immediately dropping) a full
+        // aggregator on every measurement that's headed for overflow.
+        let estimated_cost = std::mem::size_of::<T>() + CostTracker::attributes_cost(attributes);
+        let would_exceed_cost = self.tracker.current() + estimated_cost > self.max_cost;
+        let would_exceed_cardinality = self.aggregators.len() >= self.max_cardinality;
+
+        if self.overflow.is_some() || would_exceed_cost || would_exceed_cardinality {
+            return self.overflow.get_or_insert_with(new_aggregator);
+        }
+
+        let aggregator = new_aggregator();
+        let cost = aggregator.cost() + CostTracker::attributes_cost(attributes);
+        self.tracker.add(cost);
+        let entry = self.aggregators.entry(key).or_insert((aggregator, cost));
+        &mut entry.0
+    }
+
+    /// Reset the store for the next collection interval, returning the
+    /// checkpointed `(attributes, aggregator)` pairs, with the overflow
+    /// aggregator (if any) tagged with [`OVERFLOW_ATTRIBUTE_KEY`].
+    pub fn checkpoint(&mut self) -> Vec<(Vec<KeyValue>, T)> {
+        let mut out: Vec<(Vec<KeyValue>, T)> = self
+            .aggregators
+            .drain()
+            .map(|(key, (aggregator, cost))| {
+                self.tracker.subtract(cost);
+                (key.0, aggregator)
+            })
+            .collect();
+
+        if let Some(overflow) = self.overflow.take() {
+            out.push((vec![KeyValue::new(OVERFLOW_ATTRIBUTE_KEY, true)], overflow));
+        }
+
+        out
+    }
+
+    /// The cost currently tracked across all non-overflow aggregators.
+    pub fn current_cost(&self) -> usize {
+        self.tracker.current()
+    }
+
+    /// The highest cost ever tracked, for diagnostics.
+    pub fn peak_cost(&self) -> usize {
+        self.tracker.peak()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::metrics::aggregation::Aggregation;
+    use crate::metrics::sdk_api::{Descriptor, Number};
+    use opentelemetry_api::metrics::Result;
+    use opentelemetry_api::Context;
+    use std::any::Any;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use std::sync::Arc;
+
+    /// A trivial aggregator whose `cost()` grows every time `update` is
+    /// called, standing in for something like a ddsketch that accumulates
+    /// buckets over time.
+    #[derive(Debug, Default)]
+    struct GrowingAggregator {
+        updates: AtomicUsize,
+    }
+
+    impl Aggregator for GrowingAggregator {
+        fn update(
+            &self,
+            _cx: &Context,
+            _number: &Number,
+            _attributes: &[KeyValue],
+            _descriptor: &Descriptor,
+        ) -> Result<()> {
+            self.updates.fetch_add(1, AtomicOrdering::SeqCst);
+            Ok(())
+        }
+
+        fn synchronized_move(
+            &self,
+            _destination: &Arc<dyn Aggregator + Send + Sync>,
+            _descriptor: &Descriptor,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn merge(
+            &self,
+            _other: &(dyn Aggregator + Send + Sync),
+            _descriptor: &Descriptor,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn aggregation(&self) -> &dyn Aggregation {
+            unimplemented!("not exercised by these tests")
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn cost(&self) -> usize {
+            self.updates.load(AtomicOrdering::SeqCst) * 100
+        }
+    }
+
+    #[test]
+    fn cost_tracks_aggregator_growth_not_just_creation_estimate() {
+        let mut store = BoundedAggregatorStore::<GrowingAggregator>::new(usize::MAX, usize::MAX);
+        let attrs = [KeyValue::new("a", "b")];
+
+        let aggregator = store.get_or_create(&attrs, GrowingAggregator::default);
+        aggregator.updates.fetch_add(5, AtomicOrdering::SeqCst);
+        let cost_before_refresh = store.current_cost();
+
+        // Re-querying the same attribute set must re-derive the cost from
+        // the aggregator's current state, not the one-time estimate taken
+        // when it was created.
+        store.get_or_create(&attrs, GrowingAggregator::default);
+        assert!(store.current_cost() > cost_before_refresh);
+    }
+
+    #[test]
+    fn cost_limit_routes_new_attribute_sets_to_overflow() {
+        let mut store = BoundedAggregatorStore::<GrowingAggregator>::new(1, usize::MAX);
+
+        let a = store.get_or_create(&[KeyValue::new("a", "1")], GrowingAggregator::default);
+        a.updates.fetch_add(1, AtomicOrdering::SeqCst);
+        // No attribute set fits in a single byte of cost budget, so this
+        // one overflows just as the first did.
+        store.get_or_create(&[KeyValue::new("a", "2")], GrowingAggregator::default);
+
+        let checkpoint = store.checkpoint();
+        assert!(checkpoint
+            .iter()
+            .any(|(attrs, _)| attrs.iter().any(|kv| kv.key.as_str() == OVERFLOW_ATTRIBUTE_KEY)));
+    }
+
+    #[test]
+    fn overflow_path_constructs_an_aggregator_at_most_once() {
+        let mut store = BoundedAggregatorStore::<GrowingAggregator>::new(1, usize::MAX);
+        let constructions = AtomicUsize::new(0);
+        let new_aggregator = || {
+            constructions.fetch_add(1, AtomicOrdering::SeqCst);
+            GrowingAggregator::default()
+        };
+
+        // Every one of these lands in overflow (max_cost is 1 byte), which
+        // is exactly the cardinality-explosion hot path the store exists to
+        // protect; it must not build and immediately drop a fresh
+        // aggregator on every single measurement.
+        for i in 0..50 {
+            store.get_or_create(&[KeyValue::new("a", i.to_string())], new_aggregator);
+        }
+
+        assert_eq!(constructions.load(AtomicOrdering::SeqCst), 1);
+    }
+
+    #[test]
+    fn cardinality_limit_routes_new_attribute_sets_to_overflow() {
+        let mut store = BoundedAggregatorStore::<GrowingAggregator>::new(usize::MAX, 1);
+
+        store.get_or_create(&[KeyValue::new("a", "1")], GrowingAggregator::default);
+        store.get_or_create(&[KeyValue::new("a", "2")], GrowingAggregator::default);
+
+        let checkpoint = store.checkpoint();
+        assert_eq!(checkpoint.len(), 2);
+        assert!(checkpoint
+            .iter()
+            .any(|(attrs, _)| attrs.iter().any(|kv| kv.key.as_str() == OVERFLOW_ATTRIBUTE_KEY)));
+    }
+
+    #[test]
+    fn attribute_sets_with_different_ordering_hash_the_same() {
+        let mut store = BoundedAggregatorStore::<GrowingAggregator>::new(usize::MAX, usize::MAX);
+
+        store.get_or_create(
+            &[KeyValue::new("a", "1"), KeyValue::new("b", "2")],
+            GrowingAggregator::default,
+        );
+        store.get_or_create(
+            &[KeyValue::new("b", "2"), KeyValue::new("a", "1")],
+            GrowingAggregator::default,
+        );
+
+        assert_eq!(store.checkpoint().len(), 1);
+    }
+
+    #[test]
+    fn checkpoint_subtracts_tracked_cost() {
+        let mut store = BoundedAggregatorStore::<GrowingAggregator>::new(usize::MAX, usize::MAX);
+        store.get_or_create(&[KeyValue::new("a", "1")], GrowingAggregator::default);
+        assert!(store.current_cost() > 0);
+
+        store.checkpoint();
+        assert_eq!(store.current_cost(), 0);
+    }
+}
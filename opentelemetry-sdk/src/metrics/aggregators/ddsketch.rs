@@ -0,0 +1,398 @@
+use crate::export::metrics::aggregation::{Aggregation, AggregationKind, Distribution};
+use crate::metrics::{
+    aggregators::{Aggregator, Exemplar, ExemplarReservoir, DEFAULT_RESERVOIR_SIZE},
+    sdk_api::{Descriptor, Number},
+};
+use opentelemetry_api::metrics::{MetricsError, Result};
+use opentelemetry_api::{Context, KeyValue};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// The default relative accuracy used by [`ddsketch`].
+pub const DEFAULT_ALPHA: f64 = 0.01;
+
+/// Values with an absolute value smaller than this are counted as zero,
+/// avoiding `log(0)` and keeping the bucket index space bounded near zero.
+const ZERO_THRESHOLD: f64 = 1e-9;
+
+/// Create a new `DdSketchAggregator` with the default relative accuracy of
+/// 1%.
+pub fn ddsketch() -> DdSketchAggregator {
+    ddsketch_with_alpha(DEFAULT_ALPHA)
+}
+
+/// Create a new `DdSketchAggregator` with the given relative accuracy.
+///
+/// `alpha` controls the guaranteed relative error on any quantile estimate:
+/// smaller values trade more memory (more buckets) for tighter error bounds.
+pub fn ddsketch_with_alpha(alpha: f64) -> DdSketchAggregator {
+    DdSketchAggregator {
+        inner: Mutex::new(Inner {
+            alpha,
+            gamma: (1.0 + alpha) / (1.0 - alpha),
+            ..Inner::default()
+        }),
+    }
+}
+
+/// Aggregates measurements into a [DDSketch](https://arxiv.org/abs/1908.10693)
+/// relative-error histogram, giving bounded relative error on quantile
+/// queries at low memory cost compared to a fixed-boundary histogram.
+#[derive(Debug)]
+pub struct DdSketchAggregator {
+    inner: Mutex<Inner>,
+}
+
+impl Aggregation for DdSketchAggregator {
+    fn kind(&self) -> &AggregationKind {
+        &AggregationKind::EXPONENTIAL_HISTOGRAM
+    }
+}
+
+impl Aggregator for DdSketchAggregator {
+    fn aggregation(&self) -> &dyn Aggregation {
+        self
+    }
+
+    fn update(
+        &self,
+        cx: &Context,
+        number: &Number,
+        attributes: &[KeyValue],
+        descriptor: &Descriptor,
+    ) -> Result<()> {
+        let value = number.to_f64(descriptor.number_kind());
+
+        self.inner.lock().map_err(Into::into).map(|mut inner| {
+            let timestamp = cx
+                .get::<std::time::SystemTime>()
+                .copied()
+                .unwrap_or_else(opentelemetry_api::time::now);
+            inner.reservoir.offer(Exemplar::new(
+                cx,
+                number.clone(),
+                timestamp,
+                attributes.to_vec(),
+            ));
+            inner.add(value);
+        })
+    }
+
+    fn synchronized_move(
+        &self,
+        other: &Arc<dyn Aggregator + Send + Sync>,
+        _descriptor: &Descriptor,
+    ) -> Result<()> {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self.inner.lock().map_err(From::from).and_then(|mut inner| {
+                other.inner.lock().map_err(From::from).map(|mut other| {
+                    let alpha = inner.alpha;
+                    let gamma = inner.gamma;
+                    *other = std::mem::replace(&mut *inner, Inner::with_params(alpha, gamma));
+                })
+            })
+        } else {
+            Err(MetricsError::InconsistentAggregator(format!(
+                "Expected {:?}, got: {:?}",
+                self, other
+            )))
+        }
+    }
+
+    fn merge(
+        &self,
+        other: &(dyn Aggregator + Send + Sync),
+        _descriptor: &Descriptor,
+    ) -> Result<()> {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self.inner.lock().map_err(From::from).and_then(|mut inner| {
+                other
+                    .inner
+                    .lock()
+                    .map_err(From::from)
+                    .map(|mut other| inner.merge(&mut other))
+            })
+        } else {
+            Err(MetricsError::InconsistentAggregator(format!(
+                "Expected {:?}, got: {:?}",
+                self, other
+            )))
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exemplars(&self) -> Vec<Exemplar> {
+        self.inner
+            .lock()
+            .map(|inner| inner.reservoir.snapshot())
+            .unwrap_or_default()
+    }
+
+    fn cost(&self) -> usize {
+        self.inner.lock().map(|inner| inner.cost()).unwrap_or(0)
+    }
+}
+
+impl Distribution for DdSketchAggregator {
+    fn min(&self) -> Result<Number> {
+        self.inner
+            .lock()
+            .map_err(Into::into)
+            .and_then(|inner| inner.min.ok_or(MetricsError::NoDataCollected))
+            .map(|v| Number::from(v))
+    }
+
+    fn max(&self) -> Result<Number> {
+        self.inner
+            .lock()
+            .map_err(Into::into)
+            .and_then(|inner| inner.max.ok_or(MetricsError::NoDataCollected))
+            .map(|v| Number::from(v))
+    }
+
+    fn sum(&self) -> Result<Number> {
+        self.inner
+            .lock()
+            .map_err(Into::into)
+            .map(|inner| Number::from(inner.sum))
+    }
+
+    fn count(&self) -> Result<u64> {
+        self.inner.lock().map_err(Into::into).map(|inner| inner.count)
+    }
+
+    fn quantile(&self, q: f64) -> Result<Number> {
+        self.inner
+            .lock()
+            .map_err(Into::into)
+            .and_then(|inner| inner.quantile(q))
+            .map(Number::from)
+    }
+}
+
+/// Sparse per-bucket counts for one side (positive or negative) of the
+/// sketch, keyed by bucket index.
+type Buckets = BTreeMap<i32, u64>;
+
+#[derive(Debug)]
+struct Inner {
+    alpha: f64,
+    gamma: f64,
+    positive: Buckets,
+    negative: Buckets,
+    zero_count: u64,
+    count: u64,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+    reservoir: ExemplarReservoir,
+}
+
+impl Inner {
+    fn with_params(alpha: f64, gamma: f64) -> Self {
+        Inner {
+            alpha,
+            gamma,
+            ..Inner::default()
+        }
+    }
+
+    fn bucket_index(&self, v: f64) -> i32 {
+        (v.ln() / self.gamma.ln()).ceil() as i32
+    }
+
+    fn add(&mut self, v: f64) {
+        self.count += 1;
+        self.sum += v;
+        self.min = Some(self.min.map_or(v, |m| m.min(v)));
+        self.max = Some(self.max.map_or(v, |m| m.max(v)));
+
+        if v.abs() < ZERO_THRESHOLD {
+            self.zero_count += 1;
+        } else if v > 0.0 {
+            *self.positive.entry(self.bucket_index(v)).or_insert(0) += 1;
+        } else {
+            *self.negative.entry(self.bucket_index(-v)).or_insert(0) += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &mut Inner) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.zero_count += other.zero_count;
+        self.min = match (self.min, other.min) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, None) => a,
+            (None, b) => b,
+        };
+        self.max = match (self.max, other.max) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, None) => a,
+            (None, b) => b,
+        };
+        for (index, count) in other.positive.iter() {
+            *self.positive.entry(*index).or_insert(0) += count;
+        }
+        for (index, count) in other.negative.iter() {
+            *self.negative.entry(*index).or_insert(0) += count;
+        }
+        self.reservoir.merge_from(&mut other.reservoir);
+    }
+
+    /// Estimate the `q`-quantile, guaranteed to be within `alpha` relative
+    /// error of the true value.
+    fn quantile(&self, q: f64) -> Result<Number> {
+        self.quantile_f64(q).map(Number::from)
+    }
+
+    /// As [`Inner::quantile`], but returning the raw `f64` estimate rather
+    /// than a boxed [`Number`] — kept separate so tests can assert on the
+    /// numeric value directly.
+    fn quantile_f64(&self, q: f64) -> Result<f64> {
+        if self.count == 0 {
+            return Err(MetricsError::NoDataCollected);
+        }
+        // `rank` must be at least 1: q=0 asks for the smallest observed
+        // value, which lives in the first real bucket, not in an empty
+        // accumulator that hasn't consulted any bucket yet.
+        let rank = ((q * self.count as f64).ceil() as u64).max(1);
+
+        let mut accumulated = 0u64;
+        for (index, count) in self.negative.iter().rev() {
+            accumulated += count;
+            if accumulated >= rank {
+                return Ok(-self.estimate(*index));
+            }
+        }
+        accumulated += self.zero_count;
+        if accumulated >= rank {
+            return Ok(0.0);
+        }
+        for (index, count) in self.positive.iter() {
+            accumulated += count;
+            if accumulated >= rank {
+                return Ok(self.estimate(*index));
+            }
+        }
+        // Should be unreachable if `count` is maintained correctly.
+        Err(MetricsError::NoDataCollected)
+    }
+
+    fn estimate(&self, index: i32) -> f64 {
+        2.0 * self.gamma.powi(index) / (self.gamma + 1.0)
+    }
+
+    /// Approximate memory footprint: the fixed fields plus one map entry's
+    /// worth of bytes (key + count) for every populated bucket.
+    fn cost(&self) -> usize {
+        const BUCKET_ENTRY_SIZE: usize = std::mem::size_of::<(i32, u64)>();
+        std::mem::size_of::<Self>()
+            + (self.positive.len() + self.negative.len()) * BUCKET_ENTRY_SIZE
+    }
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        let alpha = DEFAULT_ALPHA;
+        Inner {
+            alpha,
+            gamma: (1.0 + alpha) / (1.0 - alpha),
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zero_count: 0,
+            count: 0,
+            sum: 0.0,
+            min: None,
+            max: None,
+            reservoir: ExemplarReservoir::new(DEFAULT_RESERVOIR_SIZE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_index_is_monotonically_increasing() {
+        let inner = Inner::with_params(DEFAULT_ALPHA, (1.0 + DEFAULT_ALPHA) / (1.0 - DEFAULT_ALPHA));
+        let mut last = inner.bucket_index(0.001);
+        for v in [0.01, 0.1, 1.0, 10.0, 100.0, 1_000.0] {
+            let index = inner.bucket_index(v);
+            assert!(index >= last, "bucket index must not decrease as v grows");
+            last = index;
+        }
+    }
+
+    #[test]
+    fn quantile_of_empty_sketch_errs() {
+        let inner = Inner::default();
+        assert!(inner.quantile_f64(0.5).is_err());
+    }
+
+    #[test]
+    fn quantile_estimate_is_within_relative_error() {
+        let mut inner = Inner::default();
+        for v in 1..=1_000 {
+            inner.add(v as f64);
+        }
+
+        // The true median of 1..=1000 is 500; the sketch must return an
+        // estimate within `alpha` relative error of it.
+        let median = inner.quantile_f64(0.5).unwrap();
+        let error = (median - 500.0).abs() / 500.0;
+        assert!(error <= DEFAULT_ALPHA, "median estimate {median} outside {DEFAULT_ALPHA} relative error");
+
+        // p100 must return (an estimate of) the maximum observed value.
+        let p100 = inner.quantile_f64(1.0).unwrap();
+        let error = (p100 - 1_000.0).abs() / 1_000.0;
+        assert!(error <= DEFAULT_ALPHA, "p100 estimate {p100} outside {DEFAULT_ALPHA} relative error");
+    }
+
+    #[test]
+    fn quantile_zero_returns_the_minimum_not_zero() {
+        // None of these values straddle zero, so q=0 must not short-circuit
+        // at an empty accumulator and return 0.0 instead of ~the minimum.
+        let mut inner = Inner::default();
+        for v in 1..=1_000 {
+            inner.add(v as f64);
+        }
+
+        let p0 = inner.quantile_f64(0.0).unwrap();
+        let error = (p0 - 1.0).abs() / 1.0;
+        assert!(error <= DEFAULT_ALPHA, "p0 estimate {p0} outside {DEFAULT_ALPHA} relative error of the minimum");
+    }
+
+    #[test]
+    fn quantile_handles_negative_and_zero_values() {
+        let mut inner = Inner::default();
+        for v in [-10.0, -1.0, 0.0, 1.0, 10.0] {
+            inner.add(v);
+        }
+        assert_eq!(inner.count, 5);
+        // The lowest quantile should land among the negative buckets.
+        assert!(inner.quantile_f64(0.01).unwrap() < 0.0);
+    }
+
+    #[test]
+    fn merge_combines_bucket_counts() {
+        let mut a = Inner::default();
+        let mut b = Inner::default();
+        for v in 1..=500 {
+            a.add(v as f64);
+        }
+        for v in 501..=1_000 {
+            b.add(v as f64);
+        }
+
+        a.merge(&mut b);
+
+        assert_eq!(a.count, 1_000);
+        assert_eq!(a.min, Some(1.0));
+        assert_eq!(a.max, Some(1_000.0));
+    }
+}
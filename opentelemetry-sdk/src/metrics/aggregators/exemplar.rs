@@ -0,0 +1,255 @@
+use crate::metrics::sdk_api::Number;
+use opentelemetry_api::trace::{SpanId, TraceContextExt, TraceId};
+use opentelemetry_api::{Context, KeyValue};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::SystemTime;
+
+/// A representative measurement captured alongside an aggregation, annotated
+/// with the trace context that was active when it was recorded.
+///
+/// Exemplars let an exporter (e.g. OTLP) attach a handful of raw data points
+/// to an otherwise aggregated time series so they can be correlated back to
+/// the trace that produced them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exemplar {
+    /// The raw measurement value.
+    pub value: Number,
+    /// The time the measurement was recorded.
+    pub timestamp: SystemTime,
+    /// The trace ID of the span that was active when the measurement was
+    /// recorded, if any.
+    pub trace_id: Option<TraceId>,
+    /// The span ID of the span that was active when the measurement was
+    /// recorded, if any.
+    pub span_id: Option<SpanId>,
+    /// The measurement's full attribute set, as passed to
+    /// [`crate::metrics::aggregators::Aggregator::update`].
+    ///
+    /// Named `filtered_attributes` to match the OTLP exemplar field it maps
+    /// to, which is meant to hold only the attributes dropped from (or
+    /// otherwise absent from) the series' own exported attribute set. This
+    /// aggregator layer has no notion of which of a measurement's
+    /// attributes the accumulator will end up exporting on the series, so
+    /// it stores the attributes as recorded; actually subtracting the
+    /// series' exported attribute set is accumulator-layer work that isn't
+    /// part of this crate yet.
+    pub filtered_attributes: Vec<KeyValue>,
+}
+
+impl Exemplar {
+    /// Build an exemplar from a measurement, pulling trace/span context out
+    /// of `cx` if it holds a valid span context.
+    ///
+    /// `filtered_attributes` is stored as given; see the field's own doc
+    /// comment for what "filtered" does and doesn't mean at this layer.
+    pub(crate) fn new(
+        cx: &Context,
+        value: Number,
+        timestamp: SystemTime,
+        filtered_attributes: Vec<KeyValue>,
+    ) -> Self {
+        let span_context = cx.span().span_context().clone();
+        let (trace_id, span_id) = if span_context.is_valid() {
+            (Some(span_context.trace_id()), Some(span_context.span_id()))
+        } else {
+            (None, None)
+        };
+
+        Exemplar {
+            value,
+            timestamp,
+            trace_id,
+            span_id,
+            filtered_attributes,
+        }
+    }
+}
+
+/// The default number of exemplars retained per collection interval.
+pub const DEFAULT_RESERVOIR_SIZE: usize = 4;
+
+/// A fixed-size exemplar reservoir using Algorithm R reservoir sampling.
+///
+/// For the `k`-th measurement observed in the current collection interval,
+/// the sample is kept in slot `rand() % (k + 1)` if that index falls within
+/// the reservoir; otherwise it is dropped. This gives every measurement in
+/// the interval an equal probability of being retained regardless of how
+/// many measurements arrive.
+#[derive(Debug, Default)]
+pub struct ExemplarReservoir {
+    slots: Vec<Option<Exemplar>>,
+    count: u64,
+}
+
+impl ExemplarReservoir {
+    /// Create a new reservoir with the given fixed capacity.
+    pub fn new(size: usize) -> Self {
+        ExemplarReservoir {
+            slots: (0..size).map(|_| None).collect(),
+            count: 0,
+        }
+    }
+
+    /// Offer a measurement to the reservoir, possibly evicting an existing
+    /// sample.
+    pub fn offer(&mut self, exemplar: Exemplar) {
+        let k = self.count;
+        self.count += 1;
+        if self.slots.is_empty() {
+            return;
+        }
+        let index = (sample_index(k, &exemplar) % (k + 1)) as usize;
+        if index < self.slots.len() {
+            self.slots[index] = Some(exemplar);
+        }
+    }
+
+    /// Return a copy of the currently held exemplars without resetting the
+    /// reservoir.
+    pub fn snapshot(&self) -> Vec<Exemplar> {
+        self.slots.iter().filter_map(|slot| slot.clone()).collect()
+    }
+
+    /// Approximate memory footprint of the reservoir: the slot vector
+    /// itself plus, for each currently retained exemplar, its fixed fields
+    /// and the heap bytes owned by its attribute list.
+    pub(crate) fn cost(&self) -> usize {
+        std::mem::size_of_val(self.slots.as_slice())
+            + self
+                .slots
+                .iter()
+                .filter_map(|slot| slot.as_ref())
+                .map(|exemplar| {
+                    std::mem::size_of::<Exemplar>()
+                        + exemplar.filtered_attributes.len() * std::mem::size_of::<KeyValue>()
+                })
+                .sum::<usize>()
+    }
+
+    /// Merge `other`'s exemplars into `self`, producing a sample that
+    /// approximates having reservoir-sampled from the concatenation of both
+    /// sides' measurement streams.
+    ///
+    /// Each side's retained exemplars are already a uniform sample of that
+    /// side's own `count` measurements, so simply concatenating them and
+    /// re-running `offer` over a fresh `0..n` index (`n` = number of
+    /// *retained* exemplars) would give a reservoir that saw a million
+    /// measurements no more weight than one that saw two. Instead, every
+    /// retained exemplar is re-offered through the same Algorithm R step as
+    /// `offer`, advancing a running stream position by `count /
+    /// retained.len()` per item so each side's sample is weighted by how
+    /// many measurements it actually represents.
+    pub fn merge_from(&mut self, other: &mut ExemplarReservoir) {
+        let capacity = self.slots.len();
+        let sides = [
+            (self.slots.iter_mut().filter_map(|s| s.take()).collect::<Vec<_>>(), self.count),
+            (other.slots.iter_mut().filter_map(|s| s.take()).collect::<Vec<_>>(), other.count),
+        ];
+
+        let mut merged: Vec<Option<Exemplar>> = (0..capacity).map(|_| None).collect();
+        let mut position = 0u64;
+        for (retained, count) in sides {
+            if retained.is_empty() {
+                continue;
+            }
+            let weight = (count / retained.len() as u64).max(1);
+            for exemplar in retained {
+                position += weight;
+                if capacity == 0 {
+                    continue;
+                }
+                let index = (sample_index(position - 1, &exemplar) % position) as usize;
+                if index < capacity {
+                    merged[index] = Some(exemplar);
+                }
+            }
+        }
+
+        self.slots = merged;
+        self.count += other.count;
+        other.count = 0;
+    }
+}
+
+/// Deterministic stand-in for `rand() % n`: spreads slot assignment across
+/// the measurement index and the exemplar's timestamp so repeated calls with
+/// the same inputs are reproducible (useful for tests), without pulling in
+/// an RNG dependency.
+fn sample_index(k: u64, exemplar: &Exemplar) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    k.hash(&mut hasher);
+    exemplar
+        .timestamp
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exemplar(seed: u64) -> Exemplar {
+        Exemplar {
+            value: Number::from(seed as f64),
+            timestamp: std::time::UNIX_EPOCH + std::time::Duration::from_nanos(seed),
+            trace_id: None,
+            span_id: None,
+            filtered_attributes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn offer_never_exceeds_capacity() {
+        let mut reservoir = ExemplarReservoir::new(4);
+        for i in 0..100 {
+            reservoir.offer(exemplar(i));
+        }
+        assert_eq!(reservoir.count, 100);
+        assert!(reservoir.snapshot().len() <= 4);
+    }
+
+    #[test]
+    fn zero_capacity_reservoir_retains_nothing() {
+        let mut reservoir = ExemplarReservoir::new(0);
+        reservoir.offer(exemplar(1));
+        reservoir.offer(exemplar(2));
+        assert_eq!(reservoir.count, 2);
+        assert!(reservoir.snapshot().is_empty());
+    }
+
+    #[test]
+    fn merge_sums_counts_from_both_sides() {
+        let mut a = ExemplarReservoir::new(4);
+        for i in 0..10 {
+            a.offer(exemplar(i));
+        }
+        let mut b = ExemplarReservoir::new(4);
+        for i in 0..1_000 {
+            b.offer(exemplar(1_000 + i));
+        }
+
+        a.merge_from(&mut b);
+
+        assert_eq!(a.count, 1_010);
+        assert_eq!(b.count, 0);
+        assert!(a.snapshot().len() <= 4);
+    }
+
+    #[test]
+    fn merge_with_empty_side_does_not_inflate_count() {
+        let mut a = ExemplarReservoir::new(4);
+        for i in 0..4 {
+            a.offer(exemplar(i));
+        }
+        let mut empty = ExemplarReservoir::new(4);
+
+        a.merge_from(&mut empty);
+
+        assert_eq!(a.count, 4);
+        assert!(a.snapshot().len() <= 4);
+    }
+}
@@ -1,13 +1,13 @@
 use crate::export::metrics::aggregation::{Aggregation, AggregationKind, LastValue};
 use crate::metrics::{
-    aggregators::Aggregator,
+    aggregators::{Aggregator, Exemplar, ExemplarReservoir, DEFAULT_RESERVOIR_SIZE},
     sdk_api::{Descriptor, Number},
 };
 use opentelemetry_api::metrics::{MetricsError, Result};
-use opentelemetry_api::Context;
+use opentelemetry_api::{Context, KeyValue};
 use std::any::Any;
 use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 /// Create a new `LastValueAggregator`
 pub fn last_value() -> LastValueAggregator {
@@ -16,6 +16,24 @@ pub fn last_value() -> LastValueAggregator {
     }
 }
 
+/// Create a new `LastValueAggregator` that treats its last-reported value as
+/// stale once `ttl` has elapsed since it was recorded.
+///
+/// This is useful for asynchronous (observer) gauges that only report
+/// intermittently: without a TTL, a gauge whose source stopped reporting
+/// would otherwise keep exporting its last value forever, as if it were
+/// still current. Once the value is stale, [`LastValue::last_value`]
+/// returns [`MetricsError::NoDataCollected`] and the series drops out of the
+/// next export instead of being reported as a flat line.
+pub fn last_value_with_ttl(ttl: Duration) -> LastValueAggregator {
+    LastValueAggregator {
+        inner: Mutex::new(Inner {
+            ttl: Some(ttl),
+            ..Inner::default()
+        }),
+    }
+}
+
 /// Aggregates last value events.
 #[derive(Debug)]
 pub struct LastValueAggregator {
@@ -33,19 +51,30 @@ impl Aggregator for LastValueAggregator {
         self
     }
 
-    fn update(&self, _cx: &Context, number: &Number, _descriptor: &Descriptor) -> Result<()> {
+    fn update(
+        &self,
+        cx: &Context,
+        number: &Number,
+        attributes: &[KeyValue],
+        _descriptor: &Descriptor,
+    ) -> Result<()> {
         self.inner.lock().map_err(Into::into).map(|mut inner| {
-            if let Some(timestamp) = _cx.get::<std::time::SystemTime>() {
-                inner.state = Some(LastValueData {
-                    value: number.clone(),
-                    timestamp: *timestamp,
-                });
-            } else {
-                inner.state = Some(LastValueData {
-                    value: number.clone(),
-                    timestamp: opentelemetry_api::time::now(),
-                });
-            }
+            let timestamp = cx
+                .get::<std::time::SystemTime>()
+                .copied()
+                .unwrap_or_else(opentelemetry_api::time::now);
+
+            inner.reservoir.offer(Exemplar::new(
+                cx,
+                number.clone(),
+                timestamp,
+                attributes.to_vec(),
+            ));
+
+            inner.state = Some(LastValueData {
+                value: number.clone(),
+                timestamp,
+            });
         })
     }
 
@@ -58,6 +87,10 @@ impl Aggregator for LastValueAggregator {
             self.inner.lock().map_err(From::from).and_then(|mut inner| {
                 other.inner.lock().map_err(From::from).map(|mut other| {
                     other.state = inner.state.take();
+                    other.reservoir = std::mem::replace(
+                        &mut inner.reservoir,
+                        ExemplarReservoir::new(DEFAULT_RESERVOIR_SIZE),
+                    );
                 })
             })
         } else {
@@ -74,20 +107,11 @@ impl Aggregator for LastValueAggregator {
     ) -> Result<()> {
         if let Some(other) = other.as_any().downcast_ref::<Self>() {
             self.inner.lock().map_err(From::from).and_then(|mut inner| {
-                other.inner.lock().map_err(From::from).map(|mut other| {
-                    match (&inner.state, &other.state) {
-                        // Take if other timestamp is greater
-                        (Some(checkpoint), Some(other_checkpoint))
-                            if other_checkpoint.timestamp > checkpoint.timestamp =>
-                        {
-                            inner.state = other.state.take()
-                        }
-                        // Take if no value exists currently
-                        (None, Some(_)) => inner.state = other.state.take(),
-                        // Otherwise done
-                        _ => (),
-                    }
-                })
+                other
+                    .inner
+                    .lock()
+                    .map_err(From::from)
+                    .map(|mut other| inner.merge(&mut other))
             })
         } else {
             Err(MetricsError::InconsistentAggregator(format!(
@@ -99,23 +123,99 @@ impl Aggregator for LastValueAggregator {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn exemplars(&self) -> Vec<Exemplar> {
+        self.inner
+            .lock()
+            .map(|inner| inner.reservoir.snapshot())
+            .unwrap_or_default()
+    }
+
+    fn cost(&self) -> usize {
+        self.inner.lock().map(|inner| inner.cost()).unwrap_or(0)
+    }
 }
 
 impl LastValue for LastValueAggregator {
     fn last_value(&self) -> Result<(Number, SystemTime)> {
-        self.inner.lock().map_err(Into::into).and_then(|inner| {
-            if let Some(checkpoint) = &inner.state {
-                Ok((checkpoint.value.clone(), checkpoint.timestamp))
-            } else {
-                Err(MetricsError::NoDataCollected)
+        self.inner.lock().map_err(Into::into).and_then(|mut inner| {
+            match &inner.state {
+                Some(checkpoint) if is_stale(checkpoint.timestamp, inner.ttl) => {
+                    inner.state = None;
+                    Err(MetricsError::NoDataCollected)
+                }
+                Some(checkpoint) => Ok((checkpoint.value.clone(), checkpoint.timestamp)),
+                None => Err(MetricsError::NoDataCollected),
             }
         })
     }
 }
 
-#[derive(Debug, Default)]
+/// Whether a value recorded at `timestamp` should be considered stale,
+/// i.e. older than `ttl` relative to now.
+fn is_stale(timestamp: SystemTime, ttl: Option<Duration>) -> bool {
+    match ttl {
+        Some(ttl) => opentelemetry_api::time::now()
+            .duration_since(timestamp)
+            .map_or(false, |age| age > ttl),
+        None => false,
+    }
+}
+
+#[derive(Debug)]
 struct Inner {
     state: Option<LastValueData>,
+    reservoir: ExemplarReservoir,
+    ttl: Option<Duration>,
+}
+
+impl Inner {
+    /// Combine `other`'s checkpointed state into `self`, respecting the TTL
+    /// (`self`'s, or `other`'s if `self` has none): a `self`/`other` pair is
+    /// expected to share the same TTL
+    /// (both come from the same instrument), but an expired `other`
+    /// checkpoint must never overwrite a fresh `self` value regardless of
+    /// timestamp ordering, since staleness takes precedence over recency.
+    fn merge(&mut self, other: &mut Inner) {
+        let ttl = self.ttl.or(other.ttl);
+        let other_is_stale = other
+            .state
+            .as_ref()
+            .map_or(false, |checkpoint| is_stale(checkpoint.timestamp, ttl));
+
+        if !other_is_stale {
+            match (&self.state, &other.state) {
+                // Take if other timestamp is greater
+                (Some(checkpoint), Some(other_checkpoint))
+                    if other_checkpoint.timestamp > checkpoint.timestamp =>
+                {
+                    self.state = other.state.take()
+                }
+                // Take if no value exists currently
+                (None, Some(_)) => self.state = other.state.take(),
+                // Otherwise done
+                _ => (),
+            }
+        }
+        self.reservoir.merge_from(&mut other.reservoir);
+    }
+
+    /// Approximate memory footprint: the fixed struct size plus the
+    /// exemplar reservoir's currently retained contents, which can hold
+    /// real attribute data once a gauge's reservoir fills up.
+    fn cost(&self) -> usize {
+        std::mem::size_of::<Self>() + self.reservoir.cost()
+    }
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        Inner {
+            state: None,
+            reservoir: ExemplarReservoir::new(DEFAULT_RESERVOIR_SIZE),
+            ttl: None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -123,3 +223,104 @@ struct LastValueData {
     value: Number,
     timestamp: SystemTime,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_at(value: f64, timestamp: SystemTime) -> Option<LastValueData> {
+        Some(LastValueData {
+            value: Number::from(value),
+            timestamp,
+        })
+    }
+
+    #[test]
+    fn merge_takes_the_newer_timestamp_when_neither_side_is_stale() {
+        let now = opentelemetry_api::time::now();
+        let older_timestamp = now - Duration::from_secs(10);
+        let mut older = Inner {
+            state: state_at(1.0, older_timestamp),
+            ..Inner::default()
+        };
+        let mut newer = Inner {
+            state: state_at(2.0, now),
+            ..Inner::default()
+        };
+
+        older.merge(&mut newer);
+
+        assert_eq!(older.state.unwrap().timestamp, now);
+    }
+
+    #[test]
+    fn merge_does_not_take_an_expired_checkpoint_even_with_a_newer_timestamp() {
+        let now = opentelemetry_api::time::now();
+        let current_timestamp = now - Duration::from_secs(5);
+        let mut current = Inner {
+            state: state_at(1.0, current_timestamp),
+            ttl: Some(Duration::from_secs(1)),
+            ..Inner::default()
+        };
+        // `stale`'s checkpoint has a *newer* timestamp than `current`'s, so
+        // timestamp ordering alone would take it, but it is older than the
+        // 1s TTL relative to now, so it must not win. (`current` is past the
+        // TTL too: with one TTL shared by both sides, the newer checkpoint
+        // can only be expired if the older one is as well.)
+        let stale_timestamp = now - Duration::from_secs(3);
+        let mut stale = Inner {
+            state: state_at(2.0, stale_timestamp),
+            ttl: Some(Duration::from_secs(1)),
+            ..Inner::default()
+        };
+
+        current.merge(&mut stale);
+
+        assert_eq!(current.state.unwrap().timestamp, current_timestamp);
+    }
+
+    #[test]
+    fn merge_takes_other_when_self_has_no_state() {
+        let now = opentelemetry_api::time::now();
+        let mut empty = Inner::default();
+        let mut other = Inner {
+            state: state_at(1.0, now),
+            ..Inner::default()
+        };
+
+        empty.merge(&mut other);
+
+        assert_eq!(empty.state.unwrap().timestamp, now);
+    }
+
+    #[test]
+    fn is_stale_respects_ttl() {
+        let now = opentelemetry_api::time::now();
+        assert!(!is_stale(now, Some(Duration::from_secs(60))));
+        assert!(is_stale(
+            now - Duration::from_secs(120),
+            Some(Duration::from_secs(60))
+        ));
+        assert!(!is_stale(now - Duration::from_secs(120), None));
+    }
+
+    #[test]
+    fn cost_grows_as_the_reservoir_fills_with_attributes() {
+        let mut inner = Inner::default();
+        let empty_cost = inner.cost();
+
+        let cx = Context::new();
+        let now = opentelemetry_api::time::now();
+        inner.reservoir.offer(Exemplar::new(
+            &cx,
+            Number::from(1.0),
+            now,
+            vec![KeyValue::new("k", "v")],
+        ));
+
+        assert!(
+            inner.cost() > empty_cost,
+            "cost() must reflect exemplars held in the reservoir, not just the fixed struct size"
+        );
+    }
+}
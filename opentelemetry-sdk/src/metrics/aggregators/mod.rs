@@ -0,0 +1,77 @@
+//! Aggregators implement a specific aggregation behavior, i.e., Sum, LastValue, Histogram.
+//!
+//! Aggregators derive outputs from a sequence of events. Single-instrument
+//! behaviors derive outputs from a sequence of measurements, while multi-instrument
+//! behaviors derive outputs from sequences of aggregator outputs.
+mod cost;
+mod ddsketch;
+mod exemplar;
+mod last_value;
+
+pub use cost::{BoundedAggregatorStore, CostTracker, OVERFLOW_ATTRIBUTE_KEY};
+pub use ddsketch::{ddsketch, ddsketch_with_alpha, DdSketchAggregator, DEFAULT_ALPHA};
+pub use exemplar::{Exemplar, ExemplarReservoir, DEFAULT_RESERVOIR_SIZE};
+pub use last_value::{last_value, last_value_with_ttl, LastValueAggregator};
+
+use crate::export::metrics::aggregation::Aggregation;
+use crate::metrics::sdk_api::{Descriptor, Number};
+use opentelemetry_api::metrics::Result;
+use opentelemetry_api::{Context, KeyValue};
+use std::any::Any;
+use std::sync::Arc;
+
+/// Aggregator implements a specific aggregation behavior, e.g. a
+/// behavior to track a sequence of updates to an instrument. Sum-only
+/// instruments commonly use a simple Sum aggregator, but for the
+/// distribution instruments, there are several possible aggregators with
+/// different cost and accuracy tradeoffs.
+pub trait Aggregator: core::fmt::Debug {
+    /// Update the aggregator with the given measurement, taking the
+    /// measurement's `Context` (for e.g. exemplar sampling) and its
+    /// attribute set into account.
+    fn update(
+        &self,
+        cx: &Context,
+        number: &Number,
+        attributes: &[KeyValue],
+        descriptor: &Descriptor,
+    ) -> Result<()>;
+
+    /// Transfer this aggregator's state into `destination` and reset self to
+    /// the zero state, ready for the next collection interval.
+    fn synchronized_move(
+        &self,
+        destination: &Arc<dyn Aggregator + Send + Sync>,
+        descriptor: &Descriptor,
+    ) -> Result<()>;
+
+    /// Combine the checkpointed state from `other` into this aggregator.
+    fn merge(&self, other: &(dyn Aggregator + Send + Sync), descriptor: &Descriptor)
+        -> Result<()>;
+
+    /// Return `self` as a `&dyn Aggregation`, for access to the aggregator's
+    /// state.
+    fn aggregation(&self) -> &dyn Aggregation;
+
+    /// Support downcasting by retrieving a reference to `self` as `Any`.
+    fn as_any(&self) -> &dyn Any;
+
+    /// The exemplars collected by this aggregator during the current
+    /// collection interval, if it maintains an exemplar reservoir.
+    ///
+    /// The default implementation returns an empty set so implementing this
+    /// is opt-in for aggregators that don't support exemplars.
+    fn exemplars(&self) -> Vec<Exemplar> {
+        Vec::new()
+    }
+
+    /// An estimate, in bytes, of this aggregator's in-memory footprint, used
+    /// by [`BoundedAggregatorStore`] to enforce a cost/cardinality limit.
+    ///
+    /// The default implementation measures `self`'s own size. Aggregators
+    /// that hold unbounded internal collections (e.g. a sparse histogram)
+    /// should override this to account for their current contents.
+    fn cost(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}